@@ -0,0 +1,20 @@
+mod sqlite;
+
+use anyhow::Result;
+pub use sqlite::SqliteDatabase;
+
+/// Maps a sticker's content hash to the `mxc://` URL it was previously uploaded to, so
+/// re-importing a pack does not re-upload stickers Matrix already has.
+///
+/// Implementations must be safe to share across the parallel import workers (see
+/// [`crate::tg::StickerPack::import`]) and must make a `put` immediately visible to a
+/// later `get` on the same instance, so a duplicate sticker within a single import that
+/// is looked up after the first copy's `put` is not uploaded again. Copies that workers
+/// look up at the same time, before either has called `put`, can each be uploaded.
+pub trait Database: Send + Sync {
+	/// Look up the mxc url a sticker with this content hash was previously uploaded to.
+	fn get(&self, hash: &[u8]) -> Result<Option<String>>;
+
+	/// Record that a sticker with this content hash was uploaded to `url`.
+	fn put(&self, hash: &[u8], url: &str) -> Result<()>;
+}
@@ -0,0 +1,48 @@
+use super::Database;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{path::Path, sync::Mutex};
+
+/// A [`Database`] backed by a local SQLite file.
+///
+/// The connection is wrapped in a [`Mutex`] so a single instance can be shared across
+/// the parallel import workers while still doing atomic keyed upserts.
+pub struct SqliteDatabase {
+	connection: Mutex<Connection>
+}
+
+impl SqliteDatabase {
+	pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let connection = Connection::open(path).context("opening sticker database")?;
+		connection
+			.execute(
+				"CREATE TABLE IF NOT EXISTS stickers (hash BLOB PRIMARY KEY, mxc_url TEXT NOT NULL)",
+				[]
+			)
+			.context("creating sticker database table")?;
+		Ok(Self { connection: Mutex::new(connection) })
+	}
+}
+
+impl Database for SqliteDatabase {
+	fn get(&self, hash: &[u8]) -> Result<Option<String>> {
+		self.connection
+			.lock()
+			.unwrap()
+			.query_row("SELECT mxc_url FROM stickers WHERE hash = ?1", params![hash], |row| row.get(0))
+			.optional()
+			.context("looking up sticker hash")
+	}
+
+	fn put(&self, hash: &[u8], url: &str) -> Result<()> {
+		self.connection
+			.lock()
+			.unwrap()
+			.execute(
+				"INSERT INTO stickers (hash, mxc_url) VALUES (?1, ?2) ON CONFLICT(hash) DO UPDATE SET mxc_url = excluded.mxc_url",
+				params![hash, url]
+			)
+			.context("storing sticker hash")?;
+		Ok(())
+	}
+}
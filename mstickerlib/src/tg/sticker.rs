@@ -5,10 +5,52 @@ use serde::Deserialize;
 pub struct Sticker {
 	pub emoji: String,
 	pub file_id: String,
-	//pub thumb: Option<PhotoSize>	TODO
+	pub thumb: Option<PhotoSize>,
 	pub width: u32,
 	pub height: u32,
-	pub is_video: bool
+	pub is_video: bool,
+	#[serde(rename = "type")]
+	pub sticker_type: StickerType,
+	pub mask_position: Option<MaskPosition>
+}
+
+/// What a sticker is used for, as reported by the Telegram Bot API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StickerType {
+	Regular,
+	Mask,
+	CustomEmoji
+}
+
+/// The face anchor a mask sticker should be placed at, with its offset and scale.
+///
+/// See <https://core.telegram.org/bots/api#maskposition>.
+#[derive(Debug, Clone, Deserialize, serde::Serialize)]
+pub struct MaskPosition {
+	pub point: MaskPoint,
+	pub x_shift: f64,
+	pub y_shift: f64,
+	pub scale: f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskPoint {
+	Forehead,
+	Eyes,
+	Mouth,
+	Chin
+}
+
+/// A Telegram-hosted thumbnail, as sent for a sticker or its pack.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PhotoSize {
+	pub file_id: String,
+	pub width: u32,
+	pub height: u32,
+	#[serde(default)]
+	pub file_size: Option<u32>
 }
 
 impl Sticker {
@@ -1,9 +1,11 @@
-use super::{sticker::Sticker, tg_get, Config, ImportConfig};
+use super::{sticker::Sticker, tg_get, Config, ImportConfig, MaskPosition};
 use crate::{database::Database, matrix};
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+use attohttpc::multipart::Multipart;
 use derive_getters::Getters;
 use futures_util::future::join_all;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 #[cfg(feature = "log")]
 use log::{info, warn};
@@ -51,8 +53,8 @@ impl StickerPack {
 		info!("import Telegram stickerpack {}({})", self.title, self.name);
 		#[cfg(feature = "log")]
 		if self.is_video {
-			warn!(
-				"sticker pack {} includes video stickers. Import of video stickers is not supported and will be skipped.",
+			info!(
+				"sticker pack {} includes video stickers, which will be converted from webm/vp9 to webp.",
 				self.name
 			);
 		}
@@ -86,6 +88,77 @@ impl StickerPack {
 	}
 }
 
+/// Create a new Telegram sticker set out of a single sticker file, owned by `user_id`.
+///
+/// The Bot API only supports building a set one sticker at a time; the rest of the pack
+/// is added afterwards with [`add_sticker_to_set`].
+pub fn create_new_sticker_set(
+	tg_config: &Config,
+	user_id: i64,
+	name: &str,
+	title: &str,
+	sticker_path: &Path,
+	emoji_list: &[String],
+	mask_position: Option<&MaskPosition>
+) -> anyhow::Result<()> {
+	attohttpc::post(format!("https://api.telegram.org/bot{}/createNewStickerSet", tg_config.bot_key))
+		.multipart(new_sticker_set_form(sticker_path, emoji_list, mask_position)?.with_text("user_id", user_id.to_string()).with_text("name", name).with_text("title", title))?
+		.send()?
+		.error_for_status()
+		.context("creating Telegram sticker set")?;
+	Ok(())
+}
+
+/// Add a sticker to an existing Telegram sticker set created by [`create_new_sticker_set`].
+pub fn add_sticker_to_set(
+	tg_config: &Config,
+	user_id: i64,
+	name: &str,
+	sticker_path: &Path,
+	emoji_list: &[String],
+	mask_position: Option<&MaskPosition>
+) -> anyhow::Result<()> {
+	attohttpc::post(format!("https://api.telegram.org/bot{}/addStickerToSet", tg_config.bot_key))
+		.multipart(new_sticker_set_form(sticker_path, emoji_list, mask_position)?.with_text("user_id", user_id.to_string()).with_text("name", name))?
+		.send()?
+		.error_for_status()
+		.context("adding sticker to Telegram sticker set")?;
+	Ok(())
+}
+
+/// Shared `multipart/form-data` body for `createNewStickerSet` and `addStickerToSet`: the
+/// sticker file plus its `InputSticker` JSON description, referencing the file by the
+/// `attach://sticker` convention documented at <https://core.telegram.org/bots/api#inputsticker>.
+fn new_sticker_set_form<'a>(
+	sticker_path: &'a Path,
+	emoji_list: &'a [String],
+	mask_position: Option<&MaskPosition>
+) -> anyhow::Result<Multipart<'a>> {
+	#[derive(Serialize)]
+	struct InputSticker<'a> {
+		sticker: &'static str,
+		format: &'static str,
+		emoji_list: &'a [String],
+		#[serde(skip_serializing_if = "Option::is_none")]
+		mask_position: Option<&'a MaskPosition>
+	}
+
+	let format = match sticker_path.extension().and_then(|extension| extension.to_str()) {
+		Some("png" | "webp") => "static",
+		Some("tgs") => "animated",
+		Some("webm") => "video",
+		_ => return Err(anyhow!("{sticker_path:?} has no recognized sticker file extension"))
+	};
+	let stickers = serde_json::to_string(&[InputSticker { sticker: "attach://sticker", format, emoji_list, mask_position }])
+		.context("encoding InputSticker")?;
+
+	Ok(Multipart::new()
+		.with_text("stickers", stickers)
+		.with_text("sticker_format", format)
+		.with_file("sticker", sticker_path)
+		.context("attaching sticker file")?)
+}
+
 /// Convert telegram stickerpack url to pack name.
 ///
 /// The url must start with `https://t.me/addstickers/`, `t.me/addstickers/` or
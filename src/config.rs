@@ -0,0 +1,48 @@
+use crate::sub_commands::import::StickerFormat;
+use serde::Deserialize;
+use std::fs;
+
+/// Name of the config file, searched for in [`crate::PROJECT_DIRS`]'s config directory.
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+	pub telegram: TelegramConfig,
+	pub matrix: MatrixConfig,
+	pub sticker: StickerConfig
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TelegramConfig {
+	pub bot_key: String
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MatrixConfig {
+	pub homeserver_url: String,
+	pub access_token: String
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StickerConfig {
+	pub transparent_color: Color,
+
+	/// Format animated (.tgs) stickers are rasterized to when `--format` is not given.
+	#[serde(default)]
+	pub format: StickerFormat
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Color {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8,
+	pub alpha: u8
+}
+
+pub fn load_config_file() -> anyhow::Result<Config> {
+	let path = crate::PROJECT_DIRS.config_dir().join(CONFIG_FILE);
+	let content = fs::read_to_string(&path)
+		.map_err(|err| anyhow::anyhow!("reading config file {}: {err}", path.display()))?;
+	toml::from_str(&content).map_err(|err| anyhow::anyhow!("parsing config file {}: {err}", path.display()))
+}
@@ -0,0 +1,79 @@
+use crate::{
+	sub_commands::import::{Sticker as ImportSticker, ThumbnailInfo},
+	tg
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// A sticker pack in the JSON shape understood by Matrix stickerpicker widgets.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StickerPack {
+	pub title: String,
+	pub id: String,
+	pub stickers: Vec<Sticker>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sticker {
+	pub body: String,
+	pub url: String,
+	pub info: StickerInfo,
+
+	/// Whether this is a regular, mask or custom-emoji sticker, and where a mask sticker
+	/// should be anchored on a face.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub sticker_type: Option<tg::StickerType>,
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub mask_position: Option<tg::MaskPosition>,
+
+	/// A lightweight preview clients can show instead of fetching the full sticker.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub thumbnail_url: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub thumbnail_info: Option<ThumbnailInfo>,
+
+	/// Local path the sticker file was saved to with `import --save`, after any format
+	/// conversion, so `export` can republish a pack without re-downloading it from Matrix.
+	/// `export` only accepts png, webp, tgs and webm files; converted gif and apng files
+	/// are rejected.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub local_path: Option<PathBuf>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StickerInfo {
+	pub w: u32,
+	pub h: u32,
+	pub size: usize,
+	pub mimetype: String
+}
+
+impl StickerPack {
+	pub fn new(pack: &tg::StickerPack, stickers: &[ImportSticker]) -> Self {
+		StickerPack {
+			title: pack.title.clone(),
+			id: format!("tg_name_{}", pack.name),
+			stickers: stickers.iter().map(Sticker::from).collect()
+		}
+	}
+}
+
+impl From<&ImportSticker> for Sticker {
+	fn from(sticker: &ImportSticker) -> Self {
+		Sticker {
+			body: sticker.emoji.clone(),
+			url: sticker.mxc_url.clone(),
+			info: StickerInfo {
+				w: sticker.width,
+				h: sticker.height,
+				size: sticker.file_size,
+				mimetype: sticker.mimetype.clone()
+			},
+			sticker_type: Some(sticker.sticker_type),
+			mask_position: sticker.mask_position.clone(),
+			thumbnail_url: sticker.thumbnail_url.clone(),
+			thumbnail_info: sticker.thumbnail_info.clone(),
+			local_path: sticker.saved_path.clone()
+		}
+	}
+}
@@ -0,0 +1,117 @@
+use crate::{config::load_config_file, stickerpicker, tg};
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+	fs,
+	path::{Path, PathBuf}
+};
+
+#[derive(Debug, Parser)]
+pub struct Opt {
+	/// Short name of the new Telegram sticker set, e.g. "my_pack_by_my_bot"
+	#[clap(required = true)]
+	name: String,
+
+	/// Human readable title for the sticker set
+	#[clap(required = true)]
+	title: String,
+
+	/// Telegram user id that will own the created sticker set
+	#[clap(required = true)]
+	user_id: i64,
+
+	/// Directory of sticker images, or an existing stickerpicker JSON file, to publish
+	#[clap(required = true)]
+	source: String
+}
+
+struct ExportSticker {
+	path: PathBuf,
+	emoji_list: Vec<String>,
+	mask_position: Option<tg::MaskPosition>
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+	let config = load_config_file()?;
+	let mut stickers = gather_stickers(&opt.source)?.into_iter();
+	let first = stickers
+		.next()
+		.ok_or_else(|| anyhow!("no stickers found in {:?}", opt.source))?;
+
+	let pb = ProgressBar::new(stickers.len() as u64 + 1);
+	pb.set_style(
+		ProgressStyle::default_bar()
+			.template("[{wide_bar:.cyan/blue}] {pos:>3}/{len} {msg}")
+			.progress_chars("#> ")
+	);
+
+	pb.println(format!("create sticker set {} with {}", opt.name, first.path.display()));
+	tg::create_new_sticker_set(
+		&config.telegram,
+		opt.user_id,
+		&opt.name,
+		&opt.title,
+		&first.path,
+		&first.emoji_list,
+		first.mask_position.as_ref()
+	)?;
+	pb.inc(1);
+
+	// the Bot API only ever builds a set one sticker at a time, so the rest are added in sequence
+	for sticker in stickers {
+		pb.println(format!("add sticker {} to {}", sticker.path.display(), opt.name));
+		tg::add_sticker_to_set(
+			&config.telegram,
+			opt.user_id,
+			&opt.name,
+			&sticker.path,
+			&sticker.emoji_list,
+			sticker.mask_position.as_ref()
+		)?;
+		pb.inc(1);
+	}
+	pb.finish();
+
+	println!("published Telegram stickerpack {}", opt.name);
+	Ok(())
+}
+
+/// Collect the stickers to publish, either from a directory of image files or from a
+/// stickerpicker JSON file previously written by the `import` subcommand.
+fn gather_stickers(source: &str) -> anyhow::Result<Vec<ExportSticker>> {
+	let path = Path::new(source);
+	if path.is_dir() {
+		let mut stickers = Vec::new();
+		for entry in fs::read_dir(path).with_context(|| format!("reading directory {source}"))? {
+			let entry = entry?;
+			let file_path = entry.path();
+			match file_path.extension().and_then(|extension| extension.to_str()) {
+				Some("png" | "webp" | "tgs" | "webm") => stickers.push(ExportSticker {
+					path: file_path,
+					emoji_list: vec!["🙂".to_owned()],
+					mask_position: None
+				}),
+				_ => continue
+			}
+		}
+		stickers.sort_by(|a, b| a.path.cmp(&b.path));
+		Ok(stickers)
+	} else {
+		let pack: stickerpicker::StickerPack = serde_json::from_str(
+			&fs::read_to_string(path).with_context(|| format!("reading stickerpicker pack {source}"))?
+		)?;
+		pack.stickers
+			.into_iter()
+			.map(|sticker| {
+				Ok(ExportSticker {
+					path: sticker.local_path.clone().ok_or_else(|| {
+						anyhow!("sticker {:?} in {source} has no local file to export", sticker.body)
+					})?,
+					emoji_list: vec![sticker.body.clone()],
+					mask_position: sticker.mask_position.clone()
+				})
+			})
+			.collect()
+	}
+}
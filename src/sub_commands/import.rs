@@ -5,23 +5,29 @@ use crate::{
 	stickerpicker, tg, DATABASE_FILE, PROJECT_DIRS
 };
 use anyhow::{anyhow, Context};
+use apng_encoder::encode_frames as apng_encode;
 use clap::Parser;
 use flate2::write::GzDecoder;
 use generic_array::GenericArray;
+use image::{imageops::FilterType, ImageFormat};
 use indicatif::{ProgressBar, ProgressStyle};
 use libwebp::WebPGetInfo as webp_get_info;
+use libwebp_anim::encode as webp_anim_encode;
 use lottie2gif::{Animation, Color};
+use mstickerlib::database::{Database, SqliteDatabase};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{digest::OutputSizeUser, Digest, Sha512};
 use std::{
 	collections::BTreeMap,
-	fs::{self, File},
-	io::{self, BufRead, Write},
+	fs,
+	io::Write,
 	path::Path,
 	process::exit
 };
 use tempfile::NamedTempFile;
+use unicode_names2::name as unicode_name;
+use webm_vp9::decode_rgba as webm_decode_rgba;
 
 #[derive(Debug, Parser)]
 pub struct Opt {
@@ -40,19 +46,162 @@ pub struct Opt {
 	/// Do not format the stickers;
 	/// The stickers can may not be shown by a matrix client
 	#[clap(short = 'F', long)]
-	noformat: bool
+	noformat: bool,
+
+	/// Format animated (.tgs) stickers are rasterized to.
+	/// webp and apng preserve per-pixel alpha; gif flattens onto `transparent_color`.
+	/// Defaults to the format configured in the config file.
+	#[clap(short = 'f', long, value_enum)]
+	format: Option<StickerFormat>,
+
+	/// Additionally emit an im.ponies custom-emoji image pack alongside the sticker pack
+	#[clap(short = 'e', long)]
+	emoji_pack: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StickerFormat {
+	#[default]
+	Gif,
+	Webp,
+	Apng
 }
 
 type Hash = GenericArray<u8, <Sha512 as OutputSizeUser>::OutputSize>;
 
-#[derive(Debug, Deserialize, Serialize)]
-struct HashUrl {
-	hash: Hash,
-	url: String
+/// Longest edge, in pixels, a generated sticker thumbnail is downscaled to.
+const THUMBNAIL_MAX_EDGE: u32 = 256;
+
+/// Longest edge, in pixels, a sticker is downscaled to for use as a custom emoji.
+const EMOJI_MAX_EDGE: u32 = 128;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailInfo {
+	pub width: u32,
+	pub height: u32,
+	pub mimetype: String,
+	pub size: usize
+}
+
+struct Thumbnail {
+	data: Vec<u8>,
+	info: ThumbnailInfo
+}
+
+/// A still RGBA frame already decoded while converting an animated or video sticker,
+/// reused to generate previews instead of re-decoding the final encoded container.
+struct DecodedFrame {
+	data: Vec<u8>,
+	width: u32,
+	height: u32
+}
+
+/// Render a static, downscaled preview of a (possibly animated) sticker.
+///
+/// Animated and video stickers are reduced to their first frame. When `decoded_frame` is
+/// given, it is used instead of re-decoding `sticker_image`: the `image` crate's decoders
+/// don't reliably support the animated webp/apng/gif containers this file can produce.
+fn make_thumbnail(decoded_frame: Option<&DecodedFrame>, sticker_image: &[u8], mimetype: &str) -> anyhow::Result<Thumbnail> {
+	match decoded_frame {
+		Some(frame) => downscale_rgba(frame, THUMBNAIL_MAX_EDGE),
+		None => downscale_sticker(sticker_image, mimetype, THUMBNAIL_MAX_EDGE)
+	}
+}
+
+/// Downscale a sticker for use as a custom emoji, unless it is already small enough to
+/// be reused as-is. See [`make_thumbnail`] for the meaning of `decoded_frame`.
+fn make_emoji_image(
+	decoded_frame: Option<&DecodedFrame>,
+	sticker_image: &[u8],
+	mimetype: &str,
+	width: u32,
+	height: u32
+) -> anyhow::Result<Option<Thumbnail>> {
+	if width <= EMOJI_MAX_EDGE && height <= EMOJI_MAX_EDGE {
+		return Ok(None);
+	}
+	match decoded_frame {
+		Some(frame) => downscale_rgba(frame, EMOJI_MAX_EDGE).map(Some),
+		None => downscale_sticker(sticker_image, mimetype, EMOJI_MAX_EDGE).map(Some)
+	}
+}
+
+fn downscale_sticker(sticker_image: &[u8], mimetype: &str, max_edge: u32) -> anyhow::Result<Thumbnail> {
+	let format = match mimetype {
+		"image/webp" => ImageFormat::WebP,
+		"image/gif" => ImageFormat::Gif,
+		"image/png" | "image/apng" => ImageFormat::Png,
+		other => return Err(anyhow!("don't know how to downscale sticker mimetype {other:?}"))
+	};
+	let frame = image::load_from_memory_with_format(sticker_image, format)?;
+	downscale_image(frame, max_edge)
+}
+
+fn downscale_rgba(frame: &DecodedFrame, max_edge: u32) -> anyhow::Result<Thumbnail> {
+	let image = image::RgbaImage::from_raw(frame.width, frame.height, frame.data.clone())
+		.ok_or_else(|| anyhow!("decoded frame dimensions do not match its pixel buffer"))?;
+	downscale_image(image::DynamicImage::ImageRgba8(image), max_edge)
+}
+
+fn downscale_image(frame: image::DynamicImage, max_edge: u32) -> anyhow::Result<Thumbnail> {
+	let max_edge = max_edge.min(frame.width().max(frame.height()));
+	let (width, height) = if frame.width() >= frame.height() {
+		(max_edge, max_edge * frame.height() / frame.width().max(1))
+	} else {
+		(max_edge * frame.width() / frame.height().max(1), max_edge)
+	};
+	let thumb = frame.resize(width.max(1), height.max(1), FilterType::Lanczos3);
+	let mut data = Vec::new();
+	thumb.write_to(&mut std::io::Cursor::new(&mut data), ImageFormat::Png)?;
+	Ok(Thumbnail {
+		info: ThumbnailInfo {
+			width: thumb.width(),
+			height: thumb.height(),
+			mimetype: "image/png".into(),
+			size: data.len()
+		},
+		data
+	})
+}
+
+/// Derive an im.ponies shortcode for a sticker, from the pack it belongs to and its emoji.
+///
+/// The index is always part of the shortcode, not just a fallback, since two stickers in
+/// the same pack commonly share an emoji and would otherwise collide and overwrite each
+/// other in the emoji pack's `images` map.
+fn emoji_shortcode(pack_name: &str, index: usize, emoji: &str) -> String {
+	let emoji_name = emoji
+		.chars()
+		.next()
+		.and_then(unicode_name)
+		.map(|name| name.to_string().to_lowercase().replace(' ', "_"))
+		.unwrap_or_else(|| "emoji".to_string());
+	format!("{pack_name}_{emoji_name}_{}", index + 1)
+}
+
+#[derive(Debug, Serialize)]
+struct EmojiPackMeta {
+	display_name: String,
+	usage: Vec<String>
+}
+
+#[derive(Debug, Serialize)]
+struct EmojiImageEntry {
+	url: String,
+	usage: Vec<String>
+}
+
+/// An `im.ponies` custom-emoji image pack, as understood by e.g. the Element clients.
+#[derive(Debug, Serialize)]
+struct EmojiPack {
+	pack: EmojiPackMeta,
+	images: BTreeMap<String, EmojiImageEntry>
 }
 
 pub struct Sticker {
 	file_hash: Hash,
+	thumbnail_hash: Option<Hash>,
 	pub mxc_url: String,
 	pub file_id: String,
 
@@ -60,7 +209,14 @@ pub struct Sticker {
 	pub width: u32,
 	pub height: u32,
 	pub file_size: usize,
-	pub mimetype: String
+	pub mimetype: String,
+	pub sticker_type: tg::StickerType,
+	pub mask_position: Option<tg::MaskPosition>,
+	pub thumbnail_url: Option<String>,
+	pub thumbnail_info: Option<ThumbnailInfo>,
+	pub emoji_shortcode: Option<String>,
+	pub emoji_url: Option<String>,
+	pub saved_path: Option<std::path::PathBuf>
 }
 
 pub fn run(mut opt: Opt) -> anyhow::Result<()> {
@@ -95,41 +251,14 @@ fn import_pack(pack: &String, config: &Config, opt: &Opt) -> anyhow::Result<()>
 	if opt.save {
 		fs::create_dir_all(format!("./stickers/{}", stickerpack.name))?;
 	}
-	let mut database_tree = BTreeMap::<GenericArray<u8, <Sha512 as OutputSizeUser>::OutputSize>, String>::new();
 	let database_file = PROJECT_DIRS.data_dir().join(DATABASE_FILE);
-	match File::open(&database_file) {
-		Ok(file) => {
-			let bufreader = std::io::BufReader::new(file);
-			for (i, line) in bufreader.lines().enumerate() {
-				let hashurl: Result<HashUrl, serde_json::Error> = serde_json::from_str(&line?);
-				match hashurl {
-					Ok(value) => {
-						database_tree.insert(value.hash, value.url);
-					},
-					Err(error) => eprintln!(
-						"Warning: Line {} of Database({}) can not be read: {:?}",
-						i + 1,
-						database_file.as_path().display(),
-						error
-					)
-				};
-			}
-		},
-		Err(error) if error.kind() == io::ErrorKind::NotFound => {
-			print!("database not found, creating a new one");
-		},
-		Err(error) => {
-			return Err(error.into());
-		}
-	};
-	let database = fs::OpenOptions::new()
-		.write(true)
-		.append(true)
-		.create(true)
-		.open(&database_file)
+	if let Some(parent) = database_file.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	let database = SqliteDatabase::open(&database_file)
 		.with_context(|| format!("WARNING: Failed to open or create database {}", database_file.display()));
-	let mut database = match database {
-		Ok(value) => Some(value),
+	let database: Option<Box<dyn Database>> = match database {
+		Ok(value) => Some(Box::new(value)),
 		Err(error) => {
 			eprintln!("{:?}", error);
 			None
@@ -152,7 +281,13 @@ fn import_pack(pack: &String, config: &Config, opt: &Opt) -> anyhow::Result<()>
 			let mut sticker_file = tg::get_sticker_file(&config.telegram, &tg_sticker)?;
 			let mut sticker_image = sticker_file.download(&config.telegram)?;
 
-			// convert sticker from lottie to gif if neccessary
+			// convert sticker from lottie to gif/webp/apng, or video to webp (gif as fallback), if neccessary
+			//
+			// `decoded_frame` keeps the first already-decoded RGBA frame around (when one
+			// exists) so the thumbnail/emoji preview below can be rendered from it instead
+			// of re-decoding the final animated webp/apng/gif container, which the `image`
+			// crate's decoders don't reliably support.
+			let mut decoded_frame: Option<DecodedFrame> = None;
 			let (width, height) = if sticker_file.file_path.ends_with(".tgs") {
 				let mut tmp = NamedTempFile::new()?;
 				{
@@ -162,34 +297,81 @@ fn import_pack(pack: &String, config: &Config, opt: &Opt) -> anyhow::Result<()>
 				tmp.flush()?;
 				let sticker = Animation::from_file(tmp.path()).ok_or_else(|| anyhow!("Failed to load sticker"))?;
 				let size = sticker.size();
+				let frames = lottie2gif::rasterize(sticker);
+				decoded_frame = Some(DecodedFrame {
+					data: frames.frame(0).to_vec(),
+					width: frames.width(),
+					height: frames.height()
+				});
 				if !opt.noformat {
 					pb.println(format!(" convert sticker {:02} {}", i, tg_sticker.emoji));
 					sticker_image.clear();
-					lottie2gif::convert(
-						sticker,
-						Color {
-							r: config.sticker.transparent_color.r,
-							g: config.sticker.transparent_color.g,
-							b: config.sticker.transparent_color.b,
-							alpha: config.sticker.transparent_color.alpha
+					match opt.format.unwrap_or(config.sticker.format) {
+						StickerFormat::Gif => {
+							frames.encode_gif(
+								Color {
+									r: config.sticker.transparent_color.r,
+									g: config.sticker.transparent_color.g,
+									b: config.sticker.transparent_color.b,
+									alpha: config.sticker.transparent_color.alpha
+								},
+								&mut sticker_image
+							)?;
+							sticker_file.file_path += ".gif";
+						},
+						StickerFormat::Webp => {
+							webp_anim_encode(&frames, &mut sticker_image)?;
+							sticker_file.file_path += ".webp";
 						},
-						&mut sticker_image
-					)?;
-					sticker_file.file_path += ".gif";
+						StickerFormat::Apng => {
+							apng_encode(&frames, &mut sticker_image)?;
+							sticker_file.file_path += ".apng";
+						}
+					};
 				}
 				(size.width() as u32, size.height() as u32)
+			} else if tg_sticker.is_video || sticker_file.file_path.ends_with(".webm") {
+				let frames = webm_decode_rgba(&sticker_image).ok_or_else(|| anyhow!("Failed to decode video sticker"))?;
+				let size = (frames.width(), frames.height());
+				decoded_frame = Some(DecodedFrame { data: frames.frame(0).to_vec(), width: size.0, height: size.1 });
+				if !opt.noformat {
+					pb.println(format!(" convert sticker {:02} {}", i + 1, tg_sticker.emoji));
+					sticker_image.clear();
+					match webp_anim_encode(&frames, &mut sticker_image) {
+						Ok(()) => sticker_file.file_path += ".webp",
+						Err(error) => {
+							pb.println(format!(
+								" WARNING: encoding video sticker {:02} {} as animated webp failed ({error}), falling back to gif",
+								i + 1,
+								tg_sticker.emoji
+							));
+							sticker_image.clear();
+							frames.encode_gif(
+								Color {
+									r: config.sticker.transparent_color.r,
+									g: config.sticker.transparent_color.g,
+									b: config.sticker.transparent_color.b,
+									alpha: config.sticker.transparent_color.alpha
+								},
+								&mut sticker_image
+							)?;
+							sticker_file.file_path += ".gif";
+						}
+					};
+				}
+				size
 			} else {
 				webp_get_info(&sticker_image)?
 			};
 
 			// store file on disk if desired
+			let mut saved_path = None;
 			if opt.save {
 				pb.println(format!("    save sticker {:02} {}", i + 1, tg_sticker.emoji));
 				let file_path: &Path = sticker_file.file_path.as_ref();
-				fs::write(
-					Path::new(&format!("./stickers/{}", stickerpack.name)).join(file_path.file_name().unwrap()),
-					&sticker_image
-				)?;
+				let dest = Path::new(&format!("./stickers/{}", stickerpack.name)).join(file_path.file_name().unwrap());
+				fs::write(&dest, &sticker_image)?;
+				saved_path = Some(dest);
 			}
 
 			let mut sticker = None;
@@ -207,28 +389,121 @@ fn import_pack(pack: &String, config: &Config, opt: &Opt) -> anyhow::Result<()>
 						.ok_or_else(|| anyhow!("ERROR: converting mimetype to string"))?
 				);
 
-				let mxc_url = if let Some(value) = database_tree.get(&hash) {
+				// `sticker_file.file_path` is only used to derive upload filenames from here on, so
+				// keep a copy instead of moving it into the first `upload_to_matrix` call and then
+				// trying to read it again for the thumbnail/emoji filenames below.
+				let base_name = sticker_file.file_path.clone();
+
+				let cached_url = database.as_deref().and_then(|db| db.get(&hash).ok().flatten());
+				let mxc_url = if let Some(value) = cached_url {
 					pb.println(format!(
 						"  upload sticker {:02} {} skipped; file with this hash was already uploaded",
 						i + 1,
 						tg_sticker.emoji
 					));
-					value.clone()
+					value
 				} else {
 					pb.println(format!("  upload sticker {:02} {}", i + 1, tg_sticker.emoji));
-					let url = upload_to_matrix(&config.matrix, sticker_file.file_path, &sticker_image, &mimetype)?;
+					let url = upload_to_matrix(&config.matrix, base_name.clone(), &sticker_image, &mimetype)?;
+					if let Some(db) = database.as_deref() {
+						db.put(&hash, &url)?;
+					}
 					url
 				};
 
+				// generate and upload a static thumbnail, deduplicated the same way as the sticker itself
+				let (thumbnail_hash, thumbnail_url, thumbnail_info) = match make_thumbnail(decoded_frame.as_ref(), &sticker_image, &mimetype) {
+					Ok(thumbnail) => {
+						let mut hasher = Sha512::new();
+						hasher.update(&thumbnail.data);
+						let thumbnail_hash = hasher.finalize();
+						let cached_url = database.as_deref().and_then(|db| db.get(&thumbnail_hash).ok().flatten());
+						let thumbnail_url = if let Some(value) = cached_url {
+							value
+						} else {
+							pb.println(format!("  upload thumbnail for sticker {:02} {}", i + 1, tg_sticker.emoji));
+							let url = upload_to_matrix(
+								&config.matrix,
+								format!("thumb_{base_name}"),
+								&thumbnail.data,
+								&thumbnail.info.mimetype
+							)?;
+							if let Some(db) = database.as_deref() {
+								db.put(&thumbnail_hash, &url)?;
+							}
+							url
+						};
+						(Some(thumbnail_hash), Some(thumbnail_url), Some(thumbnail.info))
+					},
+					Err(error) => {
+						pb.println(format!(
+							"  WARNING: failed to generate thumbnail for sticker {:02} {}: {:?}",
+							i + 1,
+							tg_sticker.emoji,
+							error
+						));
+						(None, None, None)
+					}
+				};
+
+				// downscale for use as a custom emoji, reusing the sticker upload where size allows
+				let (emoji_shortcode, emoji_url) = if opt.emoji_pack {
+					let shortcode = emoji_shortcode(&stickerpack.name, i, &tg_sticker.emoji);
+					match make_emoji_image(decoded_frame.as_ref(), &sticker_image, &mimetype, width, height) {
+						Ok(None) => (Some(shortcode), Some(mxc_url.clone())),
+						Ok(Some(emoji_image)) => {
+							let mut hasher = Sha512::new();
+							hasher.update(&emoji_image.data);
+							let emoji_hash = hasher.finalize();
+							let cached_url = database.as_deref().and_then(|db| db.get(&emoji_hash).ok().flatten());
+							let url = if let Some(value) = cached_url {
+								value
+							} else {
+								pb.println(format!("  upload emoji image for sticker {:02} {}", i + 1, tg_sticker.emoji));
+								let url = upload_to_matrix(
+									&config.matrix,
+									format!("emoji_{base_name}"),
+									&emoji_image.data,
+									&emoji_image.info.mimetype
+								)?;
+								if let Some(db) = database.as_deref() {
+									db.put(&emoji_hash, &url)?;
+								}
+								url
+							};
+							(Some(shortcode), Some(url))
+						},
+						Err(error) => {
+							pb.println(format!(
+								"  WARNING: failed to generate emoji image for sticker {:02} {}: {:?}",
+								i + 1,
+								tg_sticker.emoji,
+								error
+							));
+							(None, None)
+						}
+					}
+				} else {
+					(None, None)
+				};
+
 				sticker = Some(Sticker {
 					file_hash: hash,
+					thumbnail_hash,
 					mxc_url,
 					file_id: tg_sticker.file_id.clone(),
 					emoji: tg_sticker.emoji.clone(),
 					width,
 					height,
 					file_size: sticker_image.len(),
-					mimetype
+					mimetype,
+					sticker_type: tg_sticker.sticker_type,
+					mask_position: tg_sticker.mask_position.clone(),
+					thumbnail_url,
+					thumbnail_info,
+					emoji_shortcode,
+					emoji_url,
+					saved_path
 				});
 			}
 
@@ -246,20 +521,6 @@ fn import_pack(pack: &String, config: &Config, opt: &Opt) -> anyhow::Result<()>
 		.collect();
 	pb.finish();
 
-	// write new entries into the database
-	if !opt.noupload {
-		if let Some(ref mut db) = database {
-			for sticker in &stickers {
-				let hash_url = HashUrl {
-					hash: sticker.file_hash,
-					url: sticker.mxc_url.clone()
-				};
-				writeln!(db, "{}", serde_json::to_string(&hash_url)?)?;
-				// TODO write into database_tree
-			}
-		}
-	}
-
 	// save the stickerpack to file
 	if !stickers.is_empty() {
 		println!("save stickerpack {} to {}.json", stickerpack.title, stickerpack.name);
@@ -269,5 +530,31 @@ fn import_pack(pack: &String, config: &Config, opt: &Opt) -> anyhow::Result<()>
 			serde_json::to_string(&pack_json)?
 		)?;
 	}
+
+	// save the custom emoji pack to file
+	if opt.emoji_pack {
+		let images: BTreeMap<String, EmojiImageEntry> = stickers
+			.iter()
+			.filter_map(|sticker| {
+				Some((
+					sticker.emoji_shortcode.clone()?,
+					EmojiImageEntry { url: sticker.emoji_url.clone()?, usage: vec!["emoticon".into()] }
+				))
+			})
+			.collect();
+		if images.is_empty() {
+			eprintln!("WARNING: no stickers could be converted into a custom emoji pack");
+		} else {
+			println!("save custom emoji pack {} to {}_emoji.json", stickerpack.title, stickerpack.name);
+			let emoji_pack = EmojiPack {
+				pack: EmojiPackMeta { display_name: stickerpack.title.clone(), usage: vec!["emoticon".into()] },
+				images
+			};
+			fs::write(
+				Path::new(&format!("./{}_emoji.json", stickerpack.name)),
+				serde_json::to_string(&emoji_pack)?
+			)?;
+		}
+	}
 	Ok(())
 }